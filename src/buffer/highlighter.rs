@@ -21,12 +21,13 @@ pub enum TokenKind {
 pub struct Token {
     pub range: Range<usize>,
     pub kind: TokenKind,
+    /// Set when a comment or string contains a bidirectional control
+    /// codepoint (embedding, override, isolate or mark), which can make source text render in an
+    /// order that doesn't match its logical byte order ("Trojan Source").
+    pub bidi_control: bool,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Default)]
-pub struct State {}
-
-#[derive(Default, Clone, Copy)]
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
 pub enum Test {
     #[default]
     Always,
@@ -37,82 +38,468 @@ pub enum Test {
     NonDigit,
 }
 
-#[derive(Default, Clone, Copy)]
+/// Describes how a string-like token escapes its own closing delimiter.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct Escape {
+    /// A character that escapes whatever follows it, e.g. `\` in C strings
+    /// or `` ` `` in PowerShell double-quoted strings.
+    char: Option<u8>,
+    /// Whether doubling the closing delimiter escapes it, e.g. `''` inside
+    /// a PowerShell single-quoted string.
+    doubled: bool,
+}
+
+impl Escape {
+    const NONE: Escape = Escape { char: None, doubled: false };
+
+    const fn char(c: u8) -> Escape {
+        Escape { char: Some(c), doubled: false }
+    }
+
+    const fn doubled() -> Escape {
+        Escape { char: None, doubled: true }
+    }
+
+    const fn char_and_doubled(c: u8) -> Escape {
+        Escape { char: Some(c), doubled: true }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
 pub struct Transition {
     enter: Test,
     exit: Test,
     kind: TokenKind,
+    escape: Escape,
 }
 
 const POWERSHELL: &[&[Transition]] = &[
     // Ground state
     &[
         // Comments
-        Transition { enter: Test::Prefix("#"), exit: Test::LineEnd, kind: TokenKind::Comment },
+        Transition { enter: Test::Prefix("#"), exit: Test::LineEnd, kind: TokenKind::Comment, escape: Escape::NONE },
         Transition {
             enter: Test::Prefix("<#"),
             exit: Test::Prefix("#>"),
             kind: TokenKind::Comment,
+            escape: Escape::NONE,
         },
         // Keywords
-        Transition { enter: Test::Word("break"), exit: Test::Always, kind: TokenKind::Keyword },
-        Transition { enter: Test::Word("catch"), exit: Test::Always, kind: TokenKind::Keyword },
-        Transition { enter: Test::Word("continue"), exit: Test::Always, kind: TokenKind::Keyword },
-        Transition { enter: Test::Word("do"), exit: Test::Always, kind: TokenKind::Keyword },
-        Transition { enter: Test::Word("else"), exit: Test::Always, kind: TokenKind::Keyword },
-        Transition { enter: Test::Word("finally"), exit: Test::Always, kind: TokenKind::Keyword },
-        Transition { enter: Test::Word("foreach"), exit: Test::Always, kind: TokenKind::Keyword },
-        Transition { enter: Test::Word("function"), exit: Test::Always, kind: TokenKind::Keyword },
-        Transition { enter: Test::Word("if"), exit: Test::Always, kind: TokenKind::Keyword },
-        Transition { enter: Test::Word("return"), exit: Test::Always, kind: TokenKind::Keyword },
-        Transition { enter: Test::Word("switch"), exit: Test::Always, kind: TokenKind::Keyword },
-        Transition { enter: Test::Word("throw"), exit: Test::Always, kind: TokenKind::Keyword },
-        Transition { enter: Test::Word("try"), exit: Test::Always, kind: TokenKind::Keyword },
-        Transition { enter: Test::Word("using"), exit: Test::Always, kind: TokenKind::Keyword },
-        Transition { enter: Test::Word("while"), exit: Test::Always, kind: TokenKind::Keyword },
+        Transition { enter: Test::Word("break"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("catch"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("continue"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("do"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("else"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("finally"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("foreach"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("function"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("if"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("return"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("switch"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("throw"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("try"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("using"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("while"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
         // Operators
-        Transition { enter: Test::Prefix("=="), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix("!="), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix("&&"), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix("||"), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix("<="), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix(">="), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix("++"), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix("--"), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix("="), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix("<"), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix(">"), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix("+"), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix("-"), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix("*"), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix("/"), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix("%"), exit: Test::Always, kind: TokenKind::Operator },
-        Transition { enter: Test::Prefix("!"), exit: Test::Always, kind: TokenKind::Operator },
+        Transition { enter: Test::Prefix("=="), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("!="), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("&&"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("||"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("<="), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix(">="), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("++"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("--"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("="), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("<"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix(">"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("+"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("-"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("*"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("/"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("%"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("!"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
         // Numbers
         // Strings
-        Transition { enter: Test::Prefix("'"), exit: Test::Prefix("'"), kind: TokenKind::String },
-        Transition { enter: Test::Prefix("\""), exit: Test::Prefix("\""), kind: TokenKind::String },
+        Transition {
+            enter: Test::Prefix("'"),
+            exit: Test::Prefix("'"),
+            kind: TokenKind::String,
+            escape: Escape::doubled(),
+        },
+        Transition {
+            enter: Test::Prefix("\""),
+            exit: Test::Prefix("\""),
+            kind: TokenKind::String,
+            escape: Escape::char_and_doubled(b'`'),
+        },
         // Variables
-        Transition { enter: Test::Prefix("$"), exit: Test::NonAlpha, kind: TokenKind::Variable },
+        Transition { enter: Test::Prefix("$"), exit: Test::NonAlpha, kind: TokenKind::Variable, escape: Escape::NONE },
     ],
 ];
 
+const CLIKE: &[&[Transition]] = &[
+    // Ground state
+    &[
+        // Comments
+        Transition { enter: Test::Prefix("//"), exit: Test::LineEnd, kind: TokenKind::Comment, escape: Escape::NONE },
+        Transition {
+            enter: Test::Prefix("/*"),
+            exit: Test::Prefix("*/"),
+            kind: TokenKind::Comment,
+            escape: Escape::NONE,
+        },
+        // Keywords
+        Transition { enter: Test::Word("break"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("case"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("const"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("continue"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("default"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("do"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("else"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("enum"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("for"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("if"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("return"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("sizeof"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("static"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("struct"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("switch"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("typedef"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("union"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("void"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        Transition { enter: Test::Word("while"), exit: Test::Always, kind: TokenKind::Keyword, escape: Escape::NONE },
+        // Operators
+        Transition { enter: Test::Prefix("=="), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("!="), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("&&"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("||"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("<="), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix(">="), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("++"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("--"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("->"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("="), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("<"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix(">"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("+"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("-"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("*"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("/"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("%"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("!"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("&"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        Transition { enter: Test::Prefix("|"), exit: Test::Always, kind: TokenKind::Operator, escape: Escape::NONE },
+        // Numbers
+        // Strings
+        Transition {
+            enter: Test::Prefix("'"),
+            exit: Test::Prefix("'"),
+            kind: TokenKind::String,
+            escape: Escape::char(b'\\'),
+        },
+        Transition {
+            enter: Test::Prefix("\""),
+            exit: Test::Prefix("\""),
+            kind: TokenKind::String,
+            escape: Escape::char(b'\\'),
+        },
+    ],
+];
+
+/// A grammar recognized by the highlighter: a name, the file extensions it
+/// applies to, and the state machine table driving `Parser::parse_next_line`.
+pub struct Language {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    transitions: &'static [&'static [Transition]],
+}
+
+/// All grammars known to the highlighter. The first entry is the fallback
+/// used when no extension matches.
+pub const LANGUAGES: &[Language] = &[
+    Language { name: "PowerShell", extensions: &["ps1", "psm1", "psd1"], transitions: POWERSHELL },
+    Language {
+        name: "C",
+        extensions: &["c", "h", "cc", "cpp", "cxx", "hpp", "hxx"],
+        transitions: CLIKE,
+    },
+];
+
+/// Picks the `Language` whose `extensions` contains `extension`
+/// (case-insensitive, no leading dot). Falls back to the first entry in
+/// `LANGUAGES` if nothing matches.
+pub fn language_by_extension(extension: &str) -> &'static Language {
+    for lang in LANGUAGES {
+        if lang.extensions.iter().any(|ext| ext.eq_ignore_ascii_case(extension)) {
+            return lang;
+        }
+    }
+    &LANGUAGES[0]
+}
+
+/// The coarse category a decoded `char` falls into for the purposes of
+/// token-boundary detection.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Whitespace,
+    LineEnding,
+    Word,
+    Punctuation,
+    Other,
+}
+
+/// Categorizes a decoded Unicode scalar value for word/whitespace boundary
+/// detection, recognizing the full Unicode line-ending set (LF, CR, VT, FF,
+/// NEL, LS, PS) rather than only ASCII whitespace.
+fn categorize_char(c: char) -> CharClass {
+    match c {
+        '\n' | '\r' | '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}' => {
+            CharClass::LineEnding
+        }
+        c if c.is_whitespace() => CharClass::Whitespace,
+        c if c.is_alphanumeric() || c == '_' => CharClass::Word,
+        c if c.is_ascii_punctuation() => CharClass::Punctuation,
+        _ => CharClass::Other,
+    }
+}
+
+/// Decodes the `char` starting at the beginning of `bytes` and returns it
+/// along with its length in bytes. Invalid UTF-8 decodes to the Unicode
+/// replacement character, advancing by a single byte.
+fn decode_char(bytes: &[u8]) -> (char, usize) {
+    let end = bytes.len().min(4);
+    match std::str::from_utf8(&bytes[..end]) {
+        Ok(s) => {
+            let c = s.chars().next().unwrap();
+            (c, c.len_utf8())
+        }
+        Err(err) => match err.valid_up_to() {
+            0 => (char::REPLACEMENT_CHARACTER, 1),
+            n => {
+                let c = std::str::from_utf8(&bytes[..n]).unwrap().chars().next().unwrap();
+                (c, c.len_utf8())
+            }
+        },
+    }
+}
+
+/// The `CharClass` of the char ending at byte `pos` (i.e. immediately
+/// preceding it), found by scanning backward over UTF-8 continuation bytes
+/// to its start.
+fn char_class_before(bytes: &[u8], pos: usize) -> CharClass {
+    let mut start = pos - 1;
+    while start > 0 && (bytes[start] & 0xC0) == 0x80 {
+        start -= 1;
+    }
+    categorize_char(decode_char(&bytes[start..pos]).0)
+}
+
+/// The `CharClass` of the char starting at byte `pos`.
+fn char_class_at(bytes: &[u8], pos: usize) -> CharClass {
+    categorize_char(decode_char(&bytes[pos..]).0)
+}
+
+/// Whether `c` is a bidirectional control codepoint (embedding, override,
+/// isolate or directional mark) that can be used to make source text visually disagree with its logical byte
+/// order (the "Trojan Source" class of attacks).
+fn is_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}' | '\u{200E}' | '\u{200F}' | '\u{061C}'
+    )
+}
+
+/// Scans `bytes` for any [`is_bidi_control`] codepoint. Cheap on the common
+/// all-ASCII line: bails out on the first byte check without decoding.
+fn contains_bidi_control(bytes: &[u8]) -> bool {
+    if !bytes.iter().any(|&b| b >= 0x80) {
+        return false;
+    }
+    bytes.utf8_chunks().any(|chunk| chunk.valid().chars().any(is_bidi_control))
+}
+
+fn is_base_digit(b: u8, base: u32) -> bool {
+    match base {
+        2 => b == b'0' || b == b'1',
+        8 => (b'0'..=b'7').contains(&b),
+        16 => b.is_ascii_hexdigit(),
+        _ => b.is_ascii_digit(),
+    }
+}
+
+/// Scans a numeric literal starting at `start` and returns the offset one
+/// past its last byte, or `None` if `start` doesn't begin one. Recognizes
+/// decimal integers, `0x`/`0b`/`0o` prefixed literals, floating point
+/// numbers with a fractional part and `e`/`E` exponent, and `_` digit group
+/// separators. A lone `.` not followed by a digit is not a number.
+fn scan_number(buf: &[u8], start: usize) -> Option<usize> {
+    let len = buf.len();
+    let mut i = start;
+    let c = buf[i];
+
+    if c == b'.' {
+        if i + 1 >= len || !buf[i + 1].is_ascii_digit() {
+            return None;
+        }
+    } else if !c.is_ascii_digit() {
+        return None;
+    }
+
+    // Hex/binary/octal prefix.
+    if c == b'0' && i + 1 < len {
+        let base = match buf[i + 1] {
+            b'x' | b'X' => Some(16),
+            b'b' | b'B' => Some(2),
+            b'o' | b'O' => Some(8),
+            _ => None,
+        };
+        if let Some(base) = base {
+            let digits_start = i + 2;
+            let mut j = digits_start;
+            let mut saw_digit = false;
+            while j < len && (is_base_digit(buf[j], base) || buf[j] == b'_') {
+                saw_digit |= is_base_digit(buf[j], base);
+                j += 1;
+            }
+            return if saw_digit { Some(j) } else { Some(i + 1) };
+        }
+    }
+
+    let mut saw_int_digit = false;
+    while i < len && (buf[i].is_ascii_digit() || buf[i] == b'_') {
+        i += 1;
+        saw_int_digit = true;
+    }
+
+    if i < len && buf[i] == b'.' {
+        i += 1;
+        let frac_start = i;
+        while i < len && (buf[i].is_ascii_digit() || buf[i] == b'_') {
+            i += 1;
+        }
+        if !saw_int_digit && i == frac_start {
+            return None;
+        }
+    }
+
+    if i < len && (buf[i] == b'e' || buf[i] == b'E') {
+        let mut j = i + 1;
+        if j < len && (buf[j] == b'+' || buf[j] == b'-') {
+            j += 1;
+        }
+        if j < len && buf[j].is_ascii_digit() {
+            j += 1;
+            while j < len && (buf[j].is_ascii_digit() || buf[j] == b'_') {
+                j += 1;
+            }
+            i = j;
+        }
+    }
+
+    Some(i)
+}
+
+/// The lexer state carried across a logical line boundary. Caching the
+/// `State` at the start of every line (see [`HighlightCache`]) is what
+/// lets an edit be re-highlighted starting from the first changed line
+/// instead of from the top of the document.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct State {
+    entry: Transition,
+}
+
+/// Caches each logical line's entry [`State`], indexed by `logical_pos_y`,
+/// so that re-highlighting after an edit can resume a [`Parser`] partway
+/// through the document instead of walking it from the top.
+#[derive(Default)]
+pub struct HighlightCache {
+    entry_states: Vec<Option<State>>,
+}
+
+impl HighlightCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The state a [`Parser`] must be seeded with via [`Parser::resume`] to
+    /// correctly resume highlighting at the start of `logical_pos_y`.
+    pub fn entry_state(&self, logical_pos_y: CoordType) -> State {
+        self.entry_states.get(logical_pos_y as usize).copied().flatten().unwrap_or_default()
+    }
+
+    /// Records the entry state observed for `logical_pos_y` and reports
+    /// whether it matches what was previously cached there. `true` means
+    /// re-lexing has converged at this line: every line after it is known
+    /// to be unchanged, and the caller can stop re-parsing.
+    pub fn update(&mut self, logical_pos_y: CoordType, state: State) -> bool {
+        let idx = logical_pos_y as usize;
+        let converged = self.entry_states.get(idx) == Some(&Some(state));
+        if idx >= self.entry_states.len() {
+            self.entry_states.resize(idx + 1, None);
+        }
+        self.entry_states[idx] = Some(state);
+        converged
+    }
+
+    /// Drops every cached entry state from `first_changed_line` onward, so
+    /// a subsequent `update` can't spuriously converge against a stale,
+    /// pre-edit state.
+    pub fn invalidate_from(&mut self, first_changed_line: CoordType) {
+        self.entry_states.truncate(first_changed_line.max(0) as usize);
+    }
+}
+
 pub struct Parser<'a> {
     doc: &'a dyn ReadableDocument,
     offset: usize,
     logical_pos_y: CoordType,
+    language: &'static Language,
     state: Transition,
+    /// Whether `parse_next_line` has produced a line yet. Used instead of
+    /// `offset != 0` to decide when to bump `logical_pos_y`, because
+    /// `resume` can seed `offset` to an arbitrary non-zero value for the
+    /// very first line it parses.
+    started: bool,
 }
 
 impl<'doc> Parser<'doc> {
-    pub fn new(doc: &'doc dyn ReadableDocument, state: Transition) -> Self {
-        Self { doc, offset: 0, logical_pos_y: 0, state }
+    pub fn new(
+        doc: &'doc dyn ReadableDocument,
+        language: &'static Language,
+        state: Transition,
+    ) -> Self {
+        Self { doc, offset: 0, logical_pos_y: 0, language, state, started: false }
+    }
+
+    /// Seeds a parser to resume highlighting at `logical_pos_y`, starting
+    /// at byte `offset`, using the cached entry `state` for that line.
+    /// This is what lets [`HighlightCache`]-driven re-highlighting re-lex a
+    /// window of the document rather than walking it from the top: the
+    /// lexer is pure and resumable, taking its full state as input the
+    /// same way `entry_state` hands it back out.
+    pub fn resume(
+        doc: &'doc dyn ReadableDocument,
+        language: &'static Language,
+        logical_pos_y: CoordType,
+        offset: usize,
+        state: State,
+    ) -> Self {
+        Self { doc, offset, logical_pos_y, language, state: state.entry, started: false }
     }
 
     pub fn logical_pos_y(&self) -> CoordType {
         self.logical_pos_y
     }
 
+    /// The entry state for the line that the next call to
+    /// `parse_next_line` will produce. Cache this (keyed by
+    /// `logical_pos_y`, e.g. via [`HighlightCache::update`]) before calling
+    /// `parse_next_line` so highlighting can later be resumed here with
+    /// [`Parser::resume`].
+    pub fn entry_state(&self) -> State {
+        State { entry: self.state }
+    }
+
     pub fn parse_next_line<'a>(&mut self, arena: &'a Arena) -> Vec<Token, &'a Arena> {
         let scratch = scratch_arena(Some(arena));
         let line_offset = self.offset;
@@ -128,9 +515,10 @@ impl<'doc> Parser<'doc> {
                 return res;
             }
 
-            if self.offset != 0 {
+            if self.started {
                 self.logical_pos_y += 1;
             }
+            self.started = true;
 
             loop {
                 let (off, line) = unicode::newlines_forward(chunk, 0, 0, 1);
@@ -172,8 +560,13 @@ impl<'doc> Parser<'doc> {
 
             if matches!(self.state.enter, Test::Always) {
                 'inner: loop {
-                    while off_end < line_buf.len() && line_buf[off_end].is_ascii_whitespace() {
-                        off_end += 1;
+                    while off_end < line_buf.len() {
+                        let (c, len) = decode_char(&line_buf[off_end..]);
+                        if !matches!(categorize_char(c), CharClass::Whitespace | CharClass::LineEnding)
+                        {
+                            break;
+                        }
+                        off_end += len;
                     }
                     if off_end >= line_buf.len() {
                         break 'outer;
@@ -181,7 +574,18 @@ impl<'doc> Parser<'doc> {
 
                     off_beg = off_end;
 
-                    for t in POWERSHELL[0] {
+                    if let Some(end) = scan_number(line_buf, off_end) {
+                        off_end = end;
+                        self.state = Transition {
+                            enter: Test::Always,
+                            exit: Test::Always,
+                            kind: TokenKind::Number,
+                            escape: Escape::NONE,
+                        };
+                        break 'inner;
+                    }
+
+                    for t in self.language.transitions[0] {
                         match t.enter {
                             Test::Always => {
                                 self.state = *t;
@@ -194,12 +598,31 @@ impl<'doc> Parser<'doc> {
                                     break 'inner;
                                 }
                             }
+                            Test::Word(word) => {
+                                let before_ok = off_beg == 0
+                                    || char_class_before(line_buf, off_beg) != CharClass::Word;
+                                let after = off_beg + word.len();
+                                if before_ok
+                                    && line_buf[off_end..].starts_with(word.as_bytes())
+                                    && (after >= line_buf.len()
+                                        || char_class_at(line_buf, after) != CharClass::Word)
+                                {
+                                    off_end += word.len();
+                                    self.state = *t;
+                                    break 'inner;
+                                }
+                            }
                             _ => {}
                         }
                     }
 
-                    while off_end < line_buf.len() && !line_buf[off_end].is_ascii_whitespace() {
-                        off_end += 1;
+                    while off_end < line_buf.len() {
+                        let (c, len) = decode_char(&line_buf[off_end..]);
+                        if matches!(categorize_char(c), CharClass::Whitespace | CharClass::LineEnding)
+                        {
+                            break;
+                        }
+                        off_end += len;
                     }
                     if off_end >= line_buf.len() {
                         break 'inner;
@@ -207,35 +630,102 @@ impl<'doc> Parser<'doc> {
                 }
             }
 
+            // Captured before the match below resets `self.state` to the
+            // default (ground) transition, since the kind we want to report
+            // for this token is the one the line actually matched, not
+            // whatever state parsing continues in afterward.
+            let kind = self.state.kind;
+
             match self.state.exit {
                 Test::Always => self.state = Transition::default(),
+                Test::Word(_) => unreachable!("Word is only ever used as an enter test"),
                 Test::LineEnd => {
                     off_end = line_buf.len();
                     self.state = Transition::default();
                 }
-                Test::Prefix(prefix) => loop {
-                    while off_end < line_buf.len() && line_buf[off_end].is_ascii_whitespace() {
-                        off_end += 1;
-                    }
+                Test::Prefix(prefix) => {
+                    let escape = self.state.escape;
+                    if escape.char.is_none() && !escape.doubled {
+                        loop {
+                            while off_end < line_buf.len() {
+                                let (c, len) = decode_char(&line_buf[off_end..]);
+                                if !matches!(
+                                    categorize_char(c),
+                                    CharClass::Whitespace | CharClass::LineEnding
+                                ) {
+                                    break;
+                                }
+                                off_end += len;
+                            }
 
-                    if line_buf[off_end..].starts_with(prefix.as_bytes()) {
-                        self.state = Transition::default();
-                        off_end += prefix.len();
-                        break;
-                    }
+                            if line_buf[off_end..].starts_with(prefix.as_bytes()) {
+                                self.state = Transition::default();
+                                off_end += prefix.len();
+                                break;
+                            }
 
-                    while off_end < line_buf.len() && !line_buf[off_end].is_ascii_whitespace() {
-                        off_end += 1;
-                    }
-                    if off_end >= line_buf.len() {
-                        break;
+                            while off_end < line_buf.len() {
+                                let (c, len) = decode_char(&line_buf[off_end..]);
+                                if matches!(
+                                    categorize_char(c),
+                                    CharClass::Whitespace | CharClass::LineEnding
+                                ) {
+                                    break;
+                                }
+                                off_end += len;
+                            }
+                            if off_end >= line_buf.len() {
+                                break;
+                            }
+                        }
+                    } else {
+                        // Escape-aware scan: walk byte by byte so an escaped
+                        // delimiter can't prematurely close the token.
+                        loop {
+                            if off_end >= line_buf.len() {
+                                // Unterminated string: don't leak the string
+                                // state into the next line.
+                                self.state = Transition::default();
+                                break;
+                            }
+
+                            if escape.doubled
+                                && line_buf[off_end..].starts_with(prefix.as_bytes())
+                                && line_buf
+                                    .get(off_end + prefix.len()..)
+                                    .is_some_and(|rest| rest.starts_with(prefix.as_bytes()))
+                            {
+                                off_end += prefix.len() * 2;
+                                continue;
+                            }
+
+                            if let Some(escape_byte) = escape.char {
+                                if line_buf[off_end] == escape_byte {
+                                    off_end += 1;
+                                    if off_end < line_buf.len() {
+                                        off_end += 1;
+                                    }
+                                    continue;
+                                }
+                            }
+
+                            if line_buf[off_end..].starts_with(prefix.as_bytes()) {
+                                self.state = Transition::default();
+                                off_end += prefix.len();
+                                break;
+                            }
+
+                            off_end += 1;
+                        }
                     }
-                },
+                }
                 Test::NonAlpha => {
-                    while off_end < line_buf.len()
-                        && (line_buf[off_end].is_ascii_alphanumeric() || line_buf[off_end] >= 0x80)
-                    {
-                        off_end += 1;
+                    while off_end < line_buf.len() {
+                        let (c, len) = decode_char(&line_buf[off_end..]);
+                        if categorize_char(c) != CharClass::Word {
+                            break;
+                        }
+                        off_end += len;
                     }
                     self.state = Transition::default();
                 }
@@ -247,9 +737,13 @@ impl<'doc> Parser<'doc> {
                 }
             }
 
+            let bidi_control = matches!(kind, TokenKind::Comment | TokenKind::String)
+                && contains_bidi_control(&line_buf[off_beg..off_end]);
+
             res.push(Token {
                 range: line_offset + off_beg..line_offset + off_end,
-                kind: self.state.kind,
+                kind,
+                bidi_control,
             });
 
             if off_end >= line_buf.len() {
@@ -260,3 +754,86 @@ impl<'doc> Parser<'doc> {
         res
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestDoc<'a>(&'a [u8]);
+
+    impl<'a> ReadableDocument for TestDoc<'a> {
+        fn read_forward(&self, offset: usize) -> &[u8] {
+            self.0.get(offset..).unwrap_or(&[])
+        }
+    }
+
+    #[test]
+    fn resume_reports_the_seeded_logical_pos_y() {
+        let text = "line0\nline1\nline2\n";
+        let offset = text.find("line2").unwrap();
+        let doc = TestDoc(text.as_bytes());
+        let arena = scratch_arena(None);
+
+        let mut parser = Parser::resume(&doc, &LANGUAGES[0], 2, offset, State::default());
+        parser.parse_next_line(&arena);
+
+        assert_eq!(parser.logical_pos_y(), 2);
+    }
+
+    fn tokenize_line(text: &str, language: &'static Language) -> Vec<(String, TokenKind, bool)> {
+        let doc = TestDoc(text.as_bytes());
+        let arena = scratch_arena(None);
+        let mut parser = Parser::new(&doc, language, Transition::default());
+        let tokens = parser.parse_next_line(&arena);
+        tokens.iter().map(|t| (text[t.range.clone()].to_string(), t.kind, t.bidi_control)).collect()
+    }
+
+    #[test]
+    fn keyword_token_reports_keyword_kind() {
+        let tokens = tokenize_line("if ($x) { }", &LANGUAGES[0]);
+        assert!(
+            tokens.iter().any(|(text, kind, _)| text == "if" && matches!(kind, TokenKind::Keyword))
+        );
+    }
+
+    #[test]
+    fn number_token_reports_number_kind() {
+        let tokens = tokenize_line("$x = 0x1F", &LANGUAGES[0]);
+        assert!(
+            tokens.iter().any(|(text, kind, _)| text == "0x1F" && matches!(kind, TokenKind::Number))
+        );
+    }
+
+    #[test]
+    fn hex_prefix_without_digits_is_not_a_complete_number() {
+        assert_eq!(scan_number(b"0x_", 0), Some(1));
+        assert_eq!(scan_number(b"0x1F", 0), Some(4));
+    }
+
+    #[test]
+    fn backtick_escaped_quote_does_not_end_the_string_early() {
+        let text = "\"a `\" b\"";
+        let tokens = tokenize_line(text, &LANGUAGES[0]);
+        let strings: Vec<_> = tokens.iter().filter(|(_, kind, _)| matches!(kind, TokenKind::String)).collect();
+        assert_eq!(strings.len(), 1);
+        assert_eq!(strings[0].0, text);
+    }
+
+    #[test]
+    fn comment_with_bidi_override_is_flagged() {
+        let text = "// \u{202E}not what it looks like";
+        let tokens = tokenize_line(text, &LANGUAGES[1]);
+        let comment = tokens.iter().find(|(_, kind, _)| matches!(kind, TokenKind::Comment)).unwrap();
+        assert!(comment.2);
+    }
+
+    #[test]
+    fn keyword_boundary_treats_non_ascii_punctuation_as_a_boundary() {
+        // U+00A1 is punctuation, not a word char, so the keyword right
+        // before it should still be recognized as one.
+        let tokens = tokenize_line("if\u{00A1}", &LANGUAGES[0]);
+        assert!(
+            tokens.iter().any(|(text, kind, _)| text == "if" && matches!(kind, TokenKind::Keyword))
+        );
+    }
+}